@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::mc_socket::McSockets;
+use crate::tlv::Tlv;
+use crate::UDP_MAX_DATAGRAM;
+
+/// Runs the "who's on this multicast group?" probe: announces ourselves
+/// with a `Request`, answers anyone else's `Request` with a `Response`,
+/// and after `timeout` prints a deduplicated table of who answered us.
+pub async fn run(
+    sockets: &McSockets,
+    groups: &[SocketAddr],
+    nickname: Option<String>,
+    timeout: Duration,
+) {
+    let nonce: [u8; 4] = rand::random();
+    sockets
+        .send_to(&Tlv::Request { nonce }.encode(), groups)
+        .await
+        .unwrap();
+
+    let mut responders: HashMap<SocketAddr, Option<String>> = HashMap::new();
+    let mut buf = [0u8; UDP_MAX_DATAGRAM];
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        let received = tokio::select! {
+            received = sockets.recv_from(&mut buf) => received,
+            _ = tokio::time::sleep(remaining) => break,
+        };
+
+        let Ok((size, addr)) = received else { break };
+        let tlv = match Tlv::decode(&buf[..size]) {
+            Ok(tlv) => tlv,
+            Err(err) => {
+                eprintln!("warning: ignoring malformed discovery frame from {addr}: {err}");
+                continue;
+            }
+        };
+
+        match tlv {
+            // With multicast loopback on, this may well be the Request we
+            // just sent ourselves; don't answer our own probe.
+            Tlv::Request { nonce: req_nonce } if req_nonce == nonce => {}
+            Tlv::Request { nonce: req_nonce } => {
+                let response = Tlv::Response {
+                    nonce: req_nonce,
+                    nickname: nickname.clone(),
+                };
+                sockets.send_to(&response.encode(), groups).await.unwrap();
+            }
+            Tlv::Response {
+                nonce: resp_nonce,
+                nickname,
+            } if resp_nonce == nonce => {
+                responders.entry(addr).or_insert(nickname);
+            }
+            Tlv::Response { .. } => {}
+        }
+    }
+
+    println!("{:<24} nickname", "address");
+    for (addr, nickname) in &responders {
+        println!("{:<24} {}", addr.to_string(), nickname.as_deref().unwrap_or("-"));
+    }
+}
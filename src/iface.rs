@@ -0,0 +1,67 @@
+use std::net::Ipv4Addr;
+
+/// A local interface selected for joining a multicast group. Carries
+/// whichever of (index, v4 address) we were able to determine, since
+/// v4 joins need the interface address and v6 joins need its index.
+#[derive(Clone, Debug)]
+pub struct IfaceSel {
+    pub index: Option<u32>,
+    pub v4_addr: Option<Ipv4Addr>,
+}
+
+impl IfaceSel {
+    fn from_index(index: u32) -> Self {
+        Self {
+            index: Some(index),
+            v4_addr: None,
+        }
+    }
+
+    fn from_v4_addr(addr: Ipv4Addr) -> Self {
+        Self {
+            index: None,
+            v4_addr: Some(addr),
+        }
+    }
+
+    fn from_net_iface(iface: &default_net::interface::Interface) -> Self {
+        Self {
+            index: Some(iface.index),
+            v4_addr: iface.ipv4.first().map(|net| net.addr),
+        }
+    }
+}
+
+/// Parses `-i`: a single token, a comma-separated list of interface
+/// names/indices/v4 addresses, or the special value `all`, which joins
+/// every up, multicast-capable local interface.
+pub fn resolve(spec: &str) -> Result<Vec<IfaceSel>, Box<dyn std::error::Error + Send + Sync>> {
+    if spec.eq_ignore_ascii_case("all") {
+        return Ok(all_multicast_interfaces());
+    }
+
+    spec.split(',').map(resolve_token).collect()
+}
+
+fn resolve_token(token: &str) -> Result<IfaceSel, Box<dyn std::error::Error + Send + Sync>> {
+    if let Ok(index) = token.parse::<u32>() {
+        return Ok(IfaceSel::from_index(index));
+    }
+    if let Ok(addr) = token.parse::<Ipv4Addr>() {
+        return Ok(IfaceSel::from_v4_addr(addr));
+    }
+
+    default_net::get_interfaces()
+        .into_iter()
+        .find(|iface| iface.name == token)
+        .map(|iface| IfaceSel::from_net_iface(&iface))
+        .ok_or_else(|| format!("no such interface: {token}").into())
+}
+
+fn all_multicast_interfaces() -> Vec<IfaceSel> {
+    default_net::get_interfaces()
+        .into_iter()
+        .filter(|iface| iface.is_up() && iface.is_multicast())
+        .map(|iface| IfaceSel::from_net_iface(&iface))
+        .collect()
+}
@@ -1,101 +1,156 @@
 use clap::{Parser, ValueEnum};
-use socket2::{Domain, InterfaceIndexOrAddress, Protocol, Socket, Type};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
-use tokio::{io::AsyncBufReadExt, net::UdpSocket};
-
-#[derive(Clone, Debug, PartialEq)]
-pub enum Either<T, U> {
-    Left(T),
-    Right(U),
-}
+use std::net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::time::Duration;
+use tokio::io::AsyncBufReadExt;
+
+mod discover;
+mod iface;
+mod mc_socket;
+mod tlv;
+
+use mc_socket::McSockets;
 
 #[derive(Clone, Debug, PartialEq, ValueEnum)]
 pub enum Mode {
     Listen,
     Talk,
-}
-
-impl From<Either<u32, Ipv4Addr>> for InterfaceIndexOrAddress {
-    fn from(either: Either<u32, Ipv4Addr>) -> Self {
-        match either {
-            Either::Left(index) => InterfaceIndexOrAddress::Index(index),
-            Either::Right(addr) => InterfaceIndexOrAddress::Address(addr.into()),
-        }
-    }
-}
-
-fn parse_interface(
-    s: &str,
-) -> Result<Either<u32, Ipv4Addr>, Box<dyn std::error::Error + Send + Sync + 'static>> {
-    if let Ok(index) = s.parse::<u32>() {
-        Ok(Either::Left(index))
-    } else {
-        Ok(Either::Right(s.parse()?))
-    }
+    /// Listens and talks at once, so two peers can hold a conversation
+    /// without running separate listen/talk processes.
+    Peer,
+    /// Probes the group for other `discover`-mode participants.
+    Discover,
 }
 
 #[derive(Parser)]
 struct Config {
-    #[arg(short = 'i', value_parser = parse_interface, default_value = "0")]
-    iface: Either<u32, Ipv4Addr>,
+    /// Interface(s) to join on: a name, index, or v4 address, a
+    /// comma-separated list of those, or `all` for every up,
+    /// multicast-capable interface.
+    #[arg(short = 'i', default_value = "0")]
+    iface: String,
 
-    #[arg(short = 'a', default_value = "0.0.0.0")]
-    mc_addr: IpAddr,
+    /// Multicast group to join; may be repeated to join several groups
+    /// (v4 and v6 at once) on a single invocation.
+    #[arg(short = 'a', required = true)]
+    mc_addr: Vec<IpAddr>,
 
     #[arg(short = 'p')]
     mc_port: u16,
 
     #[arg(short = 'm', value_enum)]
     mode: Mode,
-}
 
-fn mc_socket(mc_addr: SocketAddr, iface: InterfaceIndexOrAddress) -> std::io::Result<UdpSocket> {
-    let mc_ip = mc_addr.ip();
-    assert!(mc_ip.is_multicast());
-    let synsocket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
-    synsocket.set_nonblocking(true)?;
-    synsocket.set_reuse_address(true)?;
-    synsocket.bind(&mc_addr.into())?;
+    /// Whether to hear our own transmissions come back. Defaults to off in
+    /// `peer` mode (so a peer doesn't echo itself) and on otherwise.
+    #[arg(long)]
+    loopback: Option<bool>,
 
-    let socket = UdpSocket::from_std(synsocket.into())?;
+    /// Outgoing IPv4 multicast TTL; the OS default is 1, which never
+    /// crosses a router.
+    #[arg(long)]
+    ttl: Option<u32>,
 
-    match (mc_ip, iface) {
-        (IpAddr::V4(mc_ip), InterfaceIndexOrAddress::Address(if_ip)) => {
-            socket.join_multicast_v4(mc_ip, if_ip)?;
-        }
-        (IpAddr::V6(mc_ip), InterfaceIndexOrAddress::Index(if_id)) => {
-            socket.join_multicast_v6(&mc_ip, if_id)?;
-        }
-        _ => panic!("Invalid combination of multicast IP address and interface"),
-    }
+    /// Outgoing IPv6 multicast hop limit, the v6 equivalent of TTL.
+    #[arg(long)]
+    hops: Option<u32>,
+
+    /// Nickname to advertise in `discover` mode's Response TLVs.
+    #[arg(long)]
+    nickname: Option<String>,
+
+    /// How long `discover` mode waits for responses before printing results.
+    #[arg(long, default_value_t = 1000)]
+    timeout_ms: u64,
+}
 
-    Ok(socket)
+fn groups(cfg: &Config) -> Vec<SocketAddr> {
+    cfg.mc_addr
+        .iter()
+        .map(|ip| match ip {
+            IpAddr::V4(ip) => SocketAddrV4::new(*ip, cfg.mc_port).into(),
+            IpAddr::V6(ip) => SocketAddrV6::new(*ip, cfg.mc_port, 0, 0).into(),
+        })
+        .collect()
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     let cfg = Config::parse();
+    let groups = groups(&cfg);
+    let ifaces = iface::resolve(&cfg.iface).unwrap();
+
+    let sockets = McSockets::open(&groups, &ifaces).unwrap();
+
+    if let Some(ttl) = cfg.ttl {
+        sockets.set_ttl(ttl).unwrap();
+    }
+    if let Some(hops) = cfg.hops {
+        sockets.set_hops(hops).unwrap();
+    }
+    let loopback = cfg.loopback.unwrap_or(cfg.mode != Mode::Peer);
+    sockets.set_multicast_loop(loopback).unwrap();
+
+    tokio::select! {
+        _ = run(&cfg, &sockets, &groups) => {}
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("received Ctrl-C, leaving multicast groups...");
+        }
+    }
+}
 
-    let multi = match cfg.mc_addr {
-        IpAddr::V4(ip) => SocketAddrV4::new(ip, cfg.mc_port).into(),
-        IpAddr::V6(ip) => SocketAddrV6::new(ip, cfg.mc_port, 0, 0).into(),
-    };
+/// 64 KiB, more than the largest possible UDP datagram payload, so a single
+/// read can never silently truncate a legitimate message.
+pub(crate) const UDP_MAX_DATAGRAM: usize = 65_536;
 
-    let socket = mc_socket(multi, cfg.iface.into()).unwrap();
+fn report_received(size: usize, addr: SocketAddr, buf_len: usize) {
+    println!("Received {} bytes from {:?}", size, addr);
+    if size == buf_len {
+        eprintln!("warning: datagram filled the {buf_len}-byte buffer, it may have been truncated");
+    }
+}
 
+async fn run(cfg: &Config, sockets: &McSockets, groups: &[SocketAddr]) {
     match cfg.mode {
         Mode::Listen => {
-            let mut buf = [0; 1024];
-            while let Ok((size, addr)) = socket.recv_from(&mut buf).await {
-                println!("Received {} bytes from {:?}", size, addr);
+            let mut buf = [0; UDP_MAX_DATAGRAM];
+            while let Ok((size, addr)) = sockets.recv_from(&mut buf).await {
+                report_received(size, addr, buf.len());
             }
         }
         Mode::Talk => {
             let i = tokio::io::stdin();
             let mut lines = tokio::io::BufReader::new(i).lines();
             while let Some(line) = lines.next_line().await.unwrap() {
-                socket.send_to(line.as_bytes(), multi).await.unwrap();
+                sockets.send_to(line.as_bytes(), groups).await.unwrap();
+            }
+        }
+        Mode::Peer => {
+            let mut buf = [0; UDP_MAX_DATAGRAM];
+            let i = tokio::io::stdin();
+            let mut lines = tokio::io::BufReader::new(i).lines();
+            loop {
+                tokio::select! {
+                    received = sockets.recv_from(&mut buf) => {
+                        let Ok((size, addr)) = received else { break };
+                        report_received(size, addr, buf.len());
+                    }
+                    line = lines.next_line() => {
+                        match line.unwrap() {
+                            Some(line) => sockets.send_to(line.as_bytes(), groups).await.unwrap(),
+                            None => break,
+                        }
+                    }
+                }
             }
         }
+        Mode::Discover => {
+            discover::run(
+                sockets,
+                groups,
+                cfg.nickname.clone(),
+                Duration::from_millis(cfg.timeout_ms),
+            )
+            .await;
+        }
     }
 }
@@ -0,0 +1,219 @@
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use tokio::net::UdpSocket;
+
+use crate::iface::IfaceSel;
+
+fn bind_socket(domain: Domain, port: u16) -> io::Result<Socket> {
+    let synsocket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    synsocket.set_nonblocking(true)?;
+    synsocket.set_reuse_address(true)?;
+
+    let bind_addr: SocketAddr = match domain {
+        Domain::IPV6 => {
+            synsocket.set_only_v6(true)?;
+            SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0).into()
+        }
+        _ => SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into(),
+    };
+    synsocket.bind(&bind_addr.into())?;
+
+    Ok(synsocket)
+}
+
+fn join(socket: &UdpSocket, mc_ip: IpAddr, iface: &IfaceSel) -> io::Result<()> {
+    match mc_ip {
+        IpAddr::V4(mc_ip) => {
+            // An interface given only by index (e.g. the `-i 0` default) has
+            // no known v4 address; INADDR_ANY tells the OS to pick the
+            // default route interface instead of failing the join.
+            let if_ip = iface.v4_addr.unwrap_or(Ipv4Addr::UNSPECIFIED);
+            socket.join_multicast_v4(mc_ip, if_ip)
+        }
+        IpAddr::V6(mc_ip) => {
+            let if_id = iface
+                .index
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "interface has no index"))?;
+            socket.join_multicast_v6(&mc_ip, if_id)
+        }
+    }
+}
+
+/// Joins `mc_ip` on every interface in `ifaces`, warning about (rather than
+/// aborting on) any interface that can't be joined, and returns the (group,
+/// interface) pairs that were actually joined so they can be left later.
+fn join_all_v4(
+    socket: &UdpSocket,
+    mc_ip: Ipv4Addr,
+    ifaces: &[IfaceSel],
+) -> Vec<(Ipv4Addr, Ipv4Addr)> {
+    ifaces
+        .iter()
+        .filter_map(|iface| match join(socket, IpAddr::V4(mc_ip), iface) {
+            Ok(()) => Some((mc_ip, iface.v4_addr.unwrap_or(Ipv4Addr::UNSPECIFIED))),
+            Err(err) => {
+                eprintln!("warning: failed to join {mc_ip} on interface {iface:?}: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+fn join_all_v6(socket: &UdpSocket, mc_ip: Ipv6Addr, ifaces: &[IfaceSel]) -> Vec<(Ipv6Addr, u32)> {
+    ifaces
+        .iter()
+        .filter_map(|iface| match join(socket, IpAddr::V6(mc_ip), iface) {
+            Ok(()) => iface.index.map(|if_id| (mc_ip, if_id)),
+            Err(err) => {
+                eprintln!("warning: failed to join {mc_ip} on interface {iface:?}: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// The sockets a run is operating on: a v4 one, a v6 one, or both at once
+/// (Multicol-style) when the user joined groups from both families. Leaves
+/// every group it joined when dropped.
+pub struct McSockets {
+    pub v4: Option<UdpSocket>,
+    pub v6: Option<UdpSocket>,
+    v4_memberships: Vec<(Ipv4Addr, Ipv4Addr)>,
+    v6_memberships: Vec<(Ipv6Addr, u32)>,
+}
+
+impl McSockets {
+    pub fn open(groups: &[SocketAddr], ifaces: &[IfaceSel]) -> io::Result<Self> {
+        for group in groups {
+            assert!(group.ip().is_multicast());
+        }
+
+        let v4_groups: Vec<_> = groups.iter().filter(|g| g.is_ipv4()).collect();
+        let v6_groups: Vec<_> = groups.iter().filter(|g| g.is_ipv6()).collect();
+
+        let mut v4_memberships = Vec::new();
+        let v4 = if let Some(first) = v4_groups.first() {
+            let synsocket = bind_socket(Domain::IPV4, first.port())?;
+            let socket = UdpSocket::from_std(synsocket.into())?;
+            for group in &v4_groups {
+                let IpAddr::V4(mc_ip) = group.ip() else {
+                    unreachable!("filtered to v4 groups above")
+                };
+                v4_memberships.extend(join_all_v4(&socket, mc_ip, ifaces));
+            }
+            Some(socket)
+        } else {
+            None
+        };
+
+        let mut v6_memberships = Vec::new();
+        let v6 = if let Some(first) = v6_groups.first() {
+            let synsocket = bind_socket(Domain::IPV6, first.port())?;
+            let socket = UdpSocket::from_std(synsocket.into())?;
+            for group in &v6_groups {
+                let IpAddr::V6(mc_ip) = group.ip() else {
+                    unreachable!("filtered to v6 groups above")
+                };
+                v6_memberships.extend(join_all_v6(&socket, mc_ip, ifaces));
+            }
+            Some(socket)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            v4,
+            v6,
+            v4_memberships,
+            v6_memberships,
+        })
+    }
+
+    /// Sets whether this host hears its own transmissions back. Useful to
+    /// turn off in `Peer` mode, where a single process both sends and
+    /// receives on the same joined groups.
+    pub fn set_multicast_loop(&self, enable: bool) -> io::Result<()> {
+        if let Some(v4) = &self.v4 {
+            v4.set_multicast_loop_v4(enable)?;
+        }
+        if let Some(v6) = &self.v6 {
+            v6.set_multicast_loop_v6(enable)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the outgoing IPv4 multicast TTL, scoping how many router hops
+    /// our packets can cross.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        if let Some(v4) = &self.v4 {
+            v4.set_multicast_ttl_v4(ttl)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the outgoing IPv6 multicast hop limit, the v6 equivalent of TTL.
+    /// Neither std nor tokio expose a setter for this option, so it's set
+    /// directly on the underlying fd via socket2.
+    pub fn set_hops(&self, hops: u32) -> io::Result<()> {
+        if let Some(v6) = &self.v6 {
+            SockRef::from(v6).set_multicast_hops_v6(hops)?;
+        }
+        Ok(())
+    }
+
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        match (&self.v4, &self.v6) {
+            (Some(v4), Some(v6)) => {
+                // tokio::select! builds both branch futures before polling, so
+                // `buf` can't be borrowed mutably by both recv_from calls at
+                // once; read into scratch buffers and copy the winner into it.
+                let mut v4_buf = vec![0; buf.len()];
+                let mut v6_buf = vec![0; buf.len()];
+                let (size, addr, scratch) = tokio::select! {
+                    res = v4.recv_from(&mut v4_buf) => {
+                        let (size, addr) = res?;
+                        (size, addr, v4_buf)
+                    }
+                    res = v6.recv_from(&mut v6_buf) => {
+                        let (size, addr) = res?;
+                        (size, addr, v6_buf)
+                    }
+                };
+                buf[..size].copy_from_slice(&scratch[..size]);
+                Ok((size, addr))
+            }
+            (Some(v4), None) => v4.recv_from(buf).await,
+            (None, Some(v6)) => v6.recv_from(buf).await,
+            (None, None) => unreachable!("McSockets::open always opens at least one socket"),
+        }
+    }
+
+    pub async fn send_to(&self, buf: &[u8], groups: &[SocketAddr]) -> io::Result<()> {
+        for group in groups {
+            let socket = match group {
+                SocketAddr::V4(_) => self.v4.as_ref(),
+                SocketAddr::V6(_) => self.v6.as_ref(),
+            };
+            if let Some(socket) = socket {
+                socket.send_to(buf, group).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for McSockets {
+    fn drop(&mut self) {
+        if let Some(socket) = &self.v4 {
+            for (mc_ip, if_ip) in &self.v4_memberships {
+                let _ = socket.leave_multicast_v4(*mc_ip, *if_ip);
+            }
+        }
+        if let Some(socket) = &self.v6 {
+            for (mc_ip, if_id) in &self.v6_memberships {
+                let _ = socket.leave_multicast_v6(mc_ip, *if_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+
+/// 1-byte type + 2-byte big-endian length.
+const HEADER_LEN: usize = 3;
+const NONCE_LEN: usize = 4;
+
+const TYPE_REQUEST: u8 = 1;
+const TYPE_RESPONSE: u8 = 2;
+
+/// The discovery protocol's wire frames: a TLV (type, big-endian u16
+/// length, value) carrying either a probe or an answer to one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Tlv {
+    Request {
+        nonce: [u8; NONCE_LEN],
+    },
+    Response {
+        nonce: [u8; NONCE_LEN],
+        nickname: Option<String>,
+    },
+}
+
+impl Tlv {
+    pub fn encode(&self) -> Vec<u8> {
+        let (ty, mut value) = match self {
+            Tlv::Request { nonce } => (TYPE_REQUEST, nonce.to_vec()),
+            Tlv::Response { nonce, nickname } => {
+                let mut value = nonce.to_vec();
+                if let Some(nickname) = nickname {
+                    value.extend_from_slice(nickname.as_bytes());
+                }
+                (TYPE_RESPONSE, value)
+            }
+        };
+
+        let mut buf = Vec::with_capacity(HEADER_LEN + value.len());
+        buf.push(ty);
+        buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        buf.append(&mut value);
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> io::Result<Self> {
+        if buf.len() < HEADER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "TLV frame shorter than the 3-byte header",
+            ));
+        }
+
+        let ty = buf[0];
+        let len = u16::from_be_bytes([buf[1], buf[2]]) as usize;
+        let value = buf.get(HEADER_LEN..HEADER_LEN + len).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "TLV length field exceeds the frame's actual size",
+            )
+        })?;
+
+        match ty {
+            TYPE_REQUEST => Ok(Tlv::Request {
+                nonce: read_nonce(value)?,
+            }),
+            TYPE_RESPONSE => {
+                let nonce = read_nonce(value)?;
+                let nickname = match value.get(NONCE_LEN..) {
+                    Some(bytes) if !bytes.is_empty() => {
+                        Some(String::from_utf8_lossy(bytes).into_owned())
+                    }
+                    _ => None,
+                };
+                Ok(Tlv::Response { nonce, nickname })
+            }
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown TLV type {other}"),
+            )),
+        }
+    }
+}
+
+fn read_nonce(value: &[u8]) -> io::Result<[u8; NONCE_LEN]> {
+    value
+        .get(..NONCE_LEN)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "TLV value missing its nonce"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_request() {
+        let tlv = Tlv::Request { nonce: [1, 2, 3, 4] };
+        assert_eq!(Tlv::decode(&tlv.encode()).unwrap(), tlv);
+    }
+
+    #[test]
+    fn round_trips_response_with_nickname() {
+        let tlv = Tlv::Response {
+            nonce: [5, 6, 7, 8],
+            nickname: Some("bob".to_string()),
+        };
+        assert_eq!(Tlv::decode(&tlv.encode()).unwrap(), tlv);
+    }
+
+    #[test]
+    fn round_trips_response_without_nickname() {
+        let tlv = Tlv::Response {
+            nonce: [9, 9, 9, 9],
+            nickname: None,
+        };
+        assert_eq!(Tlv::decode(&tlv.encode()).unwrap(), tlv);
+    }
+
+    #[test]
+    fn rejects_frame_shorter_than_header() {
+        assert!(Tlv::decode(&[TYPE_REQUEST, 0]).is_err());
+    }
+
+    #[test]
+    fn rejects_length_past_end_of_frame() {
+        assert!(Tlv::decode(&[TYPE_REQUEST, 0, 10, 1, 2, 3, 4]).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_type() {
+        assert!(Tlv::decode(&[0xff, 0, 4, 1, 2, 3, 4]).is_err());
+    }
+
+    #[test]
+    fn rejects_historical_crash_frame() {
+        assert!(Tlv::decode(b":V\n").is_err());
+    }
+}